@@ -2,12 +2,13 @@ mod cli;
 use crate::cli::extension;
 use crate::cli::otel::capture_telemetry;
 use crate::cli::otel::init_logging;
+use crate::cli::output::{error, info};
 use crate::cli::pact_broker_docker;
 use crate::cli::pact_broker_ruby;
 use clap::error::ErrorKind;
 use clap::ArgMatches;
-use clap_complete::{generate_to, Shell};
-use std::{process::ExitCode, str::FromStr};
+use clap_complete::{generate, generate_to, Shell};
+use std::{io::Write, process::ExitCode, str::FromStr};
 use tracing::span;
 
 /// Get known pactflow commands from the external crate
@@ -24,6 +25,9 @@ fn get_known_pactflow_commands() -> Vec<String> {
 pub fn main() -> ExitCode {
     let app = cli::build_cli();
     let matches = app.clone().try_get_matches();
+    if let Ok(m) = &matches {
+        cli::output::init(&app, m);
+    }
 
     let (
         enable_otel,
@@ -33,6 +37,8 @@ pub fn main() -> ExitCode {
         otel_exporter_endpoint,
         otel_exporter_protocol,
         log_level,
+        otel_sampler,
+        otel_sampler_ratio,
     ) = match &matches {
         Ok(m) => (
             m.get_flag("enable-otel"),
@@ -47,24 +53,23 @@ pub fn main() -> ExitCode {
             m.get_one::<String>("otel-exporter-protocol"),
             m.get_one::<String>("log-level")
                 .and_then(|lvl| lvl.parse::<tracing::Level>().ok()),
+            m.get_one::<String>("otel-sampler"),
+            m.get_one::<f64>("otel-sampler-ratio").copied(),
         ),
-        Err(_) => (false, false, false, None, None, None, None),
+        Err(_) => (false, false, false, None, None, None, None, None, None),
     };
-    let otel_config = Some(crate::cli::otel::OtelConfig {
-        exporter: otel_exporter.map(|v| v.clone()),
+    let otel_config = crate::cli::otel::OtelConfig {
+        exporter: otel_exporter,
         endpoint: otel_exporter_endpoint.cloned(),
         protocol: otel_exporter_protocol.cloned(),
         enable_otel: Some(enable_otel),
         enable_traces: Some(enable_otel_traces),
         enable_logs: Some(enable_otel_logs),
         log_level,
-    });
-    let tracer_provider = init_logging(otel_config.unwrap());
-    let _tracer_provider_dropper;
-    if tracer_provider.is_some() {
-        let tracer_provider = tracer_provider.unwrap().clone();
-        _tracer_provider_dropper = crate::cli::otel::TracerProviderDropper(tracer_provider);
-    }
+        sampler: otel_sampler.cloned(),
+        sampler_ratio: otel_sampler_ratio,
+    };
+    let _otel_providers_guard = init_logging(otel_config);
     tracing::debug!("Starting application");
     let root = span!(tracing::Level::TRACE, "pact-cli", work_units = 2);
     let _root_enter = root.enter();
@@ -97,12 +102,17 @@ pub fn main() -> ExitCode {
                         let standalone_args = args.subcommand_matches("ruby").unwrap();
                         let res = pact_broker_ruby::run(standalone_args);
                         return match res {
-                            Ok(_) => {
-                                capture_telemetry(&std::env::args().collect::<Vec<_>>(), 0, None);
-                                ExitCode::SUCCESS
+                            Ok(code) => {
+                                let exit_code = if code == ExitCode::SUCCESS { 0 } else { 1 };
+                                capture_telemetry(
+                                    &std::env::args().collect::<Vec<_>>(),
+                                    exit_code,
+                                    None,
+                                );
+                                code
                             }
                             Err(err) => {
-                                println!("{}", err);
+                                error!("{}", err);
                                 capture_telemetry(
                                     &std::env::args().collect::<Vec<_>>(),
                                     1,
@@ -135,12 +145,23 @@ pub fn main() -> ExitCode {
                 // Use tokio runtime for async extension operations
                 let rt = tokio::runtime::Runtime::new().unwrap();
                 match rt.block_on(extension::run_extension_command(args)) {
-                    Ok(_) => {
+                    Ok(code) if code == ExitCode::SUCCESS => {
                         capture_telemetry(&std::env::args().collect::<Vec<_>>(), 0, None);
                         Ok(())
                     }
+                    Ok(code) => {
+                        capture_telemetry(&std::env::args().collect::<Vec<_>>(), 1, None);
+                        Err(code)
+                    }
                     Err(e) => {
-                        eprintln!("❌ Extension error: {}", e);
+                        if cli::output::json() {
+                            println!(
+                                "{}",
+                                serde_json::json!({ "error": e.to_string(), "code": 1 })
+                            );
+                        } else {
+                            error!("❌ Extension error: {}", e);
+                        }
                         capture_telemetry(
                             &std::env::args().collect::<Vec<_>>(),
                             1,
@@ -189,7 +210,7 @@ pub fn main() -> ExitCode {
                                     };
                                 }
                                 Err(e) => {
-                                    eprintln!("❌ PactFlow extension error: {}", e);
+                                    error!("❌ PactFlow extension error: {}", e);
                                     capture_telemetry(
                                         &std::env::args().collect::<Vec<_>>(),
                                         1,
@@ -229,6 +250,13 @@ pub fn main() -> ExitCode {
                 capture_telemetry(&std::env::args().collect::<Vec<_>>(), 0, None);
                 res
             }
+            Some(("version", _)) => {
+                let version_span = span!(tracing::Level::INFO, "version");
+                let _version_enter = version_span.enter();
+                let res = print_versions();
+                capture_telemetry(&std::env::args().collect::<Vec<_>>(), 0, None);
+                res
+            }
             Some(("plugin", args)) => {
                 let plugin_span = span!(tracing::Level::INFO, "plugin");
                 let _plugin_enter = plugin_span.enter();
@@ -322,32 +350,76 @@ pub fn main() -> ExitCode {
     }
 }
 
+/// Versions of pact and each embedded component, as reported by their clap commands
+fn component_versions() -> Vec<(&'static str, String)> {
+    use clap::CommandFactory;
+    let version_of = |cmd: clap::Command| cmd.get_version().unwrap_or("unknown").to_string();
+    vec![
+        ("cli", env!("CARGO_PKG_VERSION").to_string()),
+        ("verifier", version_of(pact_verifier_cli::args::setup_app())),
+        ("mock", version_of(pact_mock_server_cli::setup_args())),
+        ("stub", version_of(pact_stub_server::build_args())),
+        (
+            "broker-cli",
+            version_of(pact_broker_cli::cli::pact_broker_client::add_pact_broker_client_command()),
+        ),
+        ("plugin-cli", version_of(pact_plugin_cli::Cli::command())),
+    ]
+}
+
+fn print_versions() -> Result<(), ExitCode> {
+    let versions = component_versions();
+    if cli::output::json() {
+        let map: serde_json::Map<String, serde_json::Value> = versions
+            .into_iter()
+            .map(|(name, version)| (name.to_string(), serde_json::Value::String(version)))
+            .collect();
+        println!("{}", serde_json::Value::Object(map));
+    } else {
+        for (name, version) in versions {
+            println!("{:<11} {}", name, version);
+        }
+    }
+    Ok(())
+}
+
 fn generate_completions(args: &ArgMatches) -> Result<(), ExitCode> {
     let shell = match args.get_one::<String>("shell") {
         Some(shell) => shell,
         None => {
-            eprintln!("Error: a shell is required");
-            return Err(ExitCode::from(1));
-        }
-    };
-    let out_dir = match args.get_one::<String>("dir") {
-        Some(dir) => dir.to_string(),
-        None => {
-            eprintln!("Error: a directory is expected");
+            error!("Error: a shell is required");
             return Err(ExitCode::from(1));
         }
     };
+    let out_dir = args
+        .get_one::<String>("dir")
+        .filter(|dir| dir.as_str() != "-");
     let mut cmd = cli::build_cli();
     let shell_enum = match Shell::from_str(shell) {
         Ok(shell_enum) => shell_enum,
         Err(_) => {
-            eprintln!("Error: invalid shell '{}'", shell);
+            error!("Error: invalid shell '{}'", shell);
             return Err(ExitCode::from(2));
         }
     };
-    match generate_to(shell_enum, &mut cmd, "pact".to_string(), &out_dir) {
+    let Some(out_dir) = out_dir else {
+        // Written by hand rather than straight to stdout, so a reader that stops early
+        // (`pact completions bash | head`) ends the command quietly instead of panicking
+        let mut script = Vec::new();
+        generate(shell_enum, &mut cmd, "pact", &mut script);
+        let mut stdout = std::io::stdout().lock();
+        return match stdout.write_all(&script).and_then(|()| stdout.flush()) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => {
+                error!("Error writing completions: {}", e);
+                Err(ExitCode::from(3))
+            }
+        };
+    };
+    match generate_to(shell_enum, &mut cmd, "pact".to_string(), out_dir) {
         Ok(path) => {
-            println!(
+            info!(
                 "ℹ️  {} shell completions for pact written to {}",
                 shell_enum,
                 path.display()
@@ -355,7 +427,7 @@ fn generate_completions(args: &ArgMatches) -> Result<(), ExitCode> {
             Ok(())
         }
         Err(e) => {
-            eprintln!("Error generating completions: {}", e);
+            error!("Error generating completions: {}", e);
             Err(ExitCode::from(3))
         }
     }
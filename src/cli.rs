@@ -1,4 +1,4 @@
-use clap::{value_parser, Arg, Command, CommandFactory};
+use clap::{builder::PossibleValue, value_parser, Arg, Command, CommandFactory};
 
 use crate::cli::{
     extension::add_extension_subcommand, pact_broker_docker::add_docker_broker_subcommand,
@@ -7,11 +7,12 @@ use crate::cli::{
 
 pub mod extension;
 pub mod otel;
+pub mod output;
 pub mod pact_broker_docker;
 pub mod pact_broker_ruby;
 
 pub fn build_cli() -> Command {
-    let app = Command::new("pact")
+    Command::new("pact")
         .about("🔗 Pact in a single binary - Mock/Stub Server, Provider Verifier, Broker Client & Plugin CLI")
         .long_about("
 
@@ -44,6 +45,29 @@ Contract testing with Pact lets you:
         .allow_external_subcommands(true)
         .external_subcommand_value_parser(value_parser!(String))
         .args(add_otel_options_args())
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .help("Only print warnings, errors and command results such as JSON")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            // Same id as the verifier's flag, so it is not propagated over it
+            Arg::new("no-colour")
+                .long("no-color")
+                .visible_alias("no-colour")
+                .help("Do not colour warnings and errors (also set by NO_COLOR)")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-emoji")
+                .long("no-emoji")
+                .help("Print text tags such as [ok] and [warn] instead of emoji in status output (implied by TERM=dumb or a non-UTF-8 locale)")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
         .subcommand(
             pact_broker_cli::cli::pact_broker_client::add_pact_broker_client_command()
             .name("broker")
@@ -53,12 +77,26 @@ Contract testing with Pact lets you:
         .args(pact_broker_cli::cli::add_logging_arguments())
         .subcommand(add_pactflow_with_extensions_subcommand())
         .subcommand(add_completions_subcommand())
+        .subcommand(add_version_subcommand())
         .subcommand(add_extension_subcommand())
         .subcommand(pact_plugin_cli::Cli::command().name("plugin"))
         .subcommand(pact_mock_server_cli::setup_args().name("mock"))
         .subcommand(pact_verifier_cli::args::setup_app().name("verifier"))
-        .subcommand(pact_stub_server::build_args().name("stub"));
-    app
+        .subcommand(pact_stub_server::build_args().name("stub"))
+}
+
+fn add_version_subcommand() -> Command {
+    Command::new("version")
+        .about("Print the versions of pact and its embedded components")
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("OUTPUT")
+                .value_parser(clap::builder::PossibleValuesParser::new(["text", "json"]))
+                .default_value("text")
+                .help("Output format"),
+        )
 }
 
 fn add_completions_subcommand() -> Command {
@@ -67,17 +105,16 @@ fn add_completions_subcommand() -> Command {
     .arg(Arg::new("shell")
         .value_name("SHELL")
         .required(true)
-        .value_parser(clap::builder::PossibleValuesParser::new(&["bash", "fish", "zsh", "powershell", "elvish"]))
+        .value_parser(clap::builder::PossibleValuesParser::new(["bash", "fish", "zsh", "powershell", "elvish"]))
         .help("The shell to generate the script for"))
     .arg(Arg::new("dir")
         .short('d')
         .long("dir")
         .value_name("DIRECTORY")
         .required(false)
-        .default_value(".")
         .num_args(1)
         .value_parser(clap::builder::NonEmptyStringValueParser::new())
-        .help("The directory to write the shell completions to, default is the current directory"))
+        .help("The directory to write the shell completions to. When omitted (or '-'), the script is written to stdout"))
 }
 
 fn add_otel_options_args() -> Vec<Arg> {
@@ -127,13 +164,40 @@ fn add_otel_options_args() -> Vec<Arg> {
             .default_value("http")
             .requires_if("otlp", "otel-exporter")
             .env("OTEL_EXPORTER_OTLP_PROTOCOL")
-            .value_parser(clap::builder::PossibleValuesParser::new(&[
+            .value_parser(clap::builder::PossibleValuesParser::new([
                 "http",
                 "http/protobuf",
             ])),
+        Arg::new("otel-sampler")
+            .long("otel-sampler")
+            .help("The OpenTelemetry trace sampler to use, defaults to OTEL_TRACES_SAMPLER or always_on")
+            .num_args(1)
+            .global(true)
+            // OTEL_TRACES_SAMPLER is read when the tracer is set up rather than through
+            // clap, so a value meant for another tool only warns instead of failing the command.
+            // Accept the standard OTEL_TRACES_SAMPLER names; pact always starts the root span
+            // itself, so the parentbased_* samplers behave like their root samplers
+            .value_parser(clap::builder::PossibleValuesParser::new([
+                PossibleValue::new("always_on").alias("parentbased_always_on"),
+                PossibleValue::new("always_off").alias("parentbased_always_off"),
+                PossibleValue::new("ratio").aliases(["traceidratio", "parentbased_traceidratio"]),
+            ])),
+        Arg::new("otel-sampler-ratio")
+            .long("otel-sampler-ratio")
+            .help("The fraction of traces to sample, from 0 to 1 (with --otel-sampler=ratio), defaults to OTEL_TRACES_SAMPLER_ARG or 1")
+            .num_args(1)
+            .global(true)
+            .value_parser(parse_sampler_ratio),
     ]
 }
 
+fn parse_sampler_ratio(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("'{}' is not a number between 0 and 1", value)),
+    }
+}
+
 fn add_pactflow_with_extensions_subcommand() -> Command {
     // Start with the base pactflow command from the external crate
     pact_broker_cli::cli::pactflow_client::add_pactflow_client_command()
@@ -1,3 +1,4 @@
+use crate::cli::output::warning;
 use opentelemetry::global;
 use opentelemetry::trace::TraceContextExt;
 use opentelemetry::KeyValue;
@@ -6,9 +7,10 @@ use opentelemetry_otlp::Protocol;
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::{
-    logs::SdkLoggerProvider, propagation::TraceContextPropagator, trace::SdkTracerProvider,
+    logs::SdkLoggerProvider,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
 };
-use opentelemetry_stdout::LogExporter;
 use std::sync::OnceLock;
 use tracing::info;
 use tracing::Level;
@@ -22,19 +24,79 @@ pub struct OtelConfig {
     pub exporter: Option<Vec<String>>,
     pub endpoint: Option<String>,
     pub protocol: Option<String>,
+    #[allow(dead_code)]
     pub enable_otel: Option<bool>,
     pub enable_traces: Option<bool>,
     pub enable_logs: Option<bool>,
     pub log_level: Option<Level>,
+    /// always_on, always_off or ratio, or one of their OTEL_TRACES_SAMPLER names
+    pub sampler: Option<String>,
+    pub sampler_ratio: Option<f64>,
 }
 
-pub struct TracerProviderDropper(pub opentelemetry_sdk::trace::SdkTracerProvider);
+/// The sampler from `--otel-sampler` and `--otel-sampler-ratio`, falling back to
+/// `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`. The flags are checked by clap,
+/// but the variables may be set for other tools, so a value pact cannot use only
+/// warns and samples everything.
+fn configured_sampler(otel_config: &OtelConfig) -> Sampler {
+    let name = match &otel_config.sampler {
+        Some(name) => name.clone(),
+        None => std::env::var("OTEL_TRACES_SAMPLER").unwrap_or_default(),
+    };
+    match name.trim() {
+        "" | "always_on" | "parentbased_always_on" => Sampler::AlwaysOn,
+        "always_off" | "parentbased_always_off" => Sampler::AlwaysOff,
+        "ratio" | "traceidratio" | "parentbased_traceidratio" => {
+            let ratio = match otel_config.sampler_ratio {
+                Some(ratio) => ratio,
+                None => match std::env::var("OTEL_TRACES_SAMPLER_ARG") {
+                    Err(_) => 1.0,
+                    Ok(arg) => match arg.trim().parse::<f64>() {
+                        Ok(ratio) if (0.0..=1.0).contains(&ratio) => ratio,
+                        _ => {
+                            warning!("⚠️  Ignoring OTEL_TRACES_SAMPLER_ARG '{}', it is not a number between 0 and 1. Sampling every trace.", arg);
+                            return Sampler::AlwaysOn;
+                        }
+                    },
+                },
+            };
+            Sampler::TraceIdRatioBased(ratio)
+        }
+        other => {
+            warning!(
+                "⚠️  Ignoring unsupported OTEL_TRACES_SAMPLER '{}'. Sampling every trace.",
+                other
+            );
+            Sampler::AlwaysOn
+        }
+    }
+}
 
-impl Drop for TracerProviderDropper {
+/// Flushes and shuts down the OpenTelemetry providers when dropped at the end of `main`,
+/// so short-lived commands do not lose buffered spans or log records
+#[derive(Default)]
+pub struct OtelProvidersGuard {
+    pub tracer_provider: Option<SdkTracerProvider>,
+    pub logger_provider: Option<SdkLoggerProvider>,
+}
+
+impl Drop for OtelProvidersGuard {
     fn drop(&mut self) {
-        match self.0.force_flush() {
-            Ok(_) => (),
-            Err(e) => eprintln!("Failed to flush OpenTelemetry tracing: {e}"),
+        if let Some(provider) = &self.tracer_provider {
+            if let Err(e) = provider.force_flush() {
+                eprintln!("Failed to flush OpenTelemetry tracing: {e}");
+            }
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to shut down OpenTelemetry tracing: {e}");
+            }
+        }
+        if let Some(provider) = &self.logger_provider {
+            if let Err(e) = provider.force_flush() {
+                eprintln!("Failed to flush OpenTelemetry logs: {e}");
+            }
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to shut down OpenTelemetry logs: {e}");
+            }
         }
     }
 }
@@ -59,11 +121,11 @@ fn get_resource() -> Resource {
         .clone()
 }
 
-pub fn init_logging(otel_config: OtelConfig) -> Option<SdkTracerProvider> {
+pub fn init_logging(otel_config: OtelConfig) -> OtelProvidersGuard {
     // If log_level is None, disable logs and tracing
     if otel_config.log_level.is_none() {
         info!("Log level not set, skipping logging and tracing initialization.");
-        return None;
+        return OtelProvidersGuard::default();
     }
     global::set_text_map_propagator(TraceContextPropagator::new());
     let resource = get_resource();
@@ -81,38 +143,23 @@ pub fn init_logging(otel_config: OtelConfig) -> Option<SdkTracerProvider> {
     );
 
     let mut tracer_provider: Option<SdkTracerProvider> = None;
+    let mut logger_provider: Option<SdkLoggerProvider> = None;
 
     // OTEL trace output
     if otel_config.enable_traces.unwrap_or(false) {
-        let otlp_exporter = if let Some(exporters) = &otel_config.exporter {
-            if exporters.iter().any(|e| e == "otlp") {
-                let endpoint = otel_config
-                    .endpoint
-                    .unwrap_or_else(|| "http://localhost:4318".to_string());
-                let protocol = otel_config.protocol.unwrap_or_else(|| "http".to_string());
-                let exporter = match protocol.as_str() {
-                    "grpc" => opentelemetry_otlp::SpanExporter::builder()
-                        .with_tonic()
-                        .with_endpoint(endpoint.to_string())
-                        .build()
-                        .expect("Failed to configure grpc exporter"),
-                    _ => opentelemetry_otlp::SpanExporter::builder()
-                        .with_http()
-                        .with_protocol(Protocol::HttpBinary)
-                        .build()
-                        .expect("Failed to configure http exporter"),
-                };
-                Some(exporter)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let otlp_exporter = otel_config
+            .exporter
+            .as_ref()
+            .filter(|exporters| exporters.iter().any(|e| e == "otlp"))
+            .and_then(|_| otlp_span_exporter(&otel_config));
+
+        let sampler = configured_sampler(&otel_config);
 
         // Add OTLP exporter as batch if present
         tracer_provider = if let Some(exporters_list) = &otel_config.exporter {
-            let mut builder = SdkTracerProvider::builder().with_resource(resource.clone());
+            let mut builder = SdkTracerProvider::builder()
+                .with_resource(resource.clone())
+                .with_sampler(sampler);
 
             if let Some(exporter) = otlp_exporter {
                 builder = builder.with_batch_exporter(exporter);
@@ -133,6 +180,7 @@ pub fn init_logging(otel_config: OtelConfig) -> Option<SdkTracerProvider> {
             Some(
                 SdkTracerProvider::builder()
                     .with_resource(resource.clone())
+                    .with_sampler(sampler)
                     .build(),
             )
         };
@@ -147,29 +195,27 @@ pub fn init_logging(otel_config: OtelConfig) -> Option<SdkTracerProvider> {
         layers.push(Box::new(telemetry));
     }
 
-    // // OTEL log output
+    // OTEL log output
     if otel_config.enable_logs.unwrap_or(false) {
-        let otel_log_stdout_exporter = opentelemetry_stdout::LogExporter::default();
+        let exporters = otel_config.exporter.clone().unwrap_or_default();
+        let use_otlp = exporters.iter().any(|e| e == "otlp");
+        let mut builder = SdkLoggerProvider::builder().with_resource(get_resource());
 
-        let otel_logger_provider = if otel_config.enable_logs.unwrap_or(false) {
-            let otel_otlp_stdout_exporter = opentelemetry_otlp::LogExporter::builder()
-                .with_http()
-                .with_protocol(Protocol::HttpBinary)
-                .build()
-                .expect("Failed to create log exporter");
-            SdkLoggerProvider::builder()
-                .with_resource(get_resource())
-                .with_simple_exporter(otel_log_stdout_exporter)
-                .with_batch_exporter(otel_otlp_stdout_exporter)
-                .build()
-        } else {
-            SdkLoggerProvider::builder()
-                .with_resource(get_resource())
-                .with_simple_exporter(otel_log_stdout_exporter)
-                .build()
-        };
+        if use_otlp {
+            if let Some(exporter) = otlp_log_exporter(&otel_config) {
+                builder = builder.with_batch_exporter(exporter);
+            }
+        }
+
+        // stdout is used when requested, and as the fallback when there is no OTLP exporter
+        if !use_otlp || exporters.iter().any(|e| e == "stdout" || e == "console") {
+            builder = builder.with_simple_exporter(opentelemetry_stdout::LogExporter::default());
+        }
+
+        let otel_logger_provider = builder.build();
         let otel_layer = OpenTelemetryTracingBridge::new(&otel_logger_provider);
         layers.push(Box::new(otel_layer));
+        logger_provider = Some(otel_logger_provider);
     }
     // create a layered subscriber
     let subscriber = tracing_subscriber::registry().with(layers);
@@ -179,16 +225,214 @@ pub fn init_logging(otel_config: OtelConfig) -> Option<SdkTracerProvider> {
             "Global tracing subscriber already set, attaching layers is not supported at runtime."
         );
     }
-    tracer_provider
+    OtelProvidersGuard {
+        tracer_provider,
+        logger_provider,
+    }
+}
+
+/// The OTLP/HTTP URL for one signal. `--otel-exporter-endpoint` is the collector's base
+/// URL, as with `OTEL_EXPORTER_OTLP_ENDPOINT`, so the signal path is added unless it is
+/// already there.
+fn otlp_signal_endpoint(endpoint: &str, signal_path: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(signal_path) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint, signal_path)
+    }
+}
+
+/// `--otel-exporter-protocol` only accepts the HTTP protobuf names
+fn otlp_protocol(otel_config: &OtelConfig) -> Protocol {
+    match otel_config.protocol.as_deref() {
+        None | Some("http") | Some("http/protobuf") => Protocol::HttpBinary,
+        Some(other) => {
+            eprintln!("Unsupported OTLP protocol {other}, using http/protobuf");
+            Protocol::HttpBinary
+        }
+    }
+}
+
+/// The OTLP trace exporter, or `None` (after printing why) when it cannot be configured
+fn otlp_span_exporter(otel_config: &OtelConfig) -> Option<opentelemetry_otlp::SpanExporter> {
+    let mut builder = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(otlp_protocol(otel_config));
+    if let Some(endpoint) = &otel_config.endpoint {
+        builder = builder.with_endpoint(otlp_signal_endpoint(endpoint, "/v1/traces"));
+    }
+    builder
+        .build()
+        .inspect_err(|e| eprintln!("Failed to configure the OTLP trace exporter: {e}"))
+        .ok()
+}
+
+/// The OTLP log exporter, configured like [`otlp_span_exporter`]
+fn otlp_log_exporter(otel_config: &OtelConfig) -> Option<opentelemetry_otlp::LogExporter> {
+    let mut builder = opentelemetry_otlp::LogExporter::builder()
+        .with_http()
+        .with_protocol(otlp_protocol(otel_config));
+    if let Some(endpoint) = &otel_config.endpoint {
+        builder = builder.with_endpoint(otlp_signal_endpoint(endpoint, "/v1/logs"));
+    }
+    builder
+        .build()
+        .inspect_err(|e| eprintln!("Failed to configure the OTLP log exporter: {e}"))
+        .ok()
+}
+
+/// Fragments of long flag names whose values must never reach the telemetry backend
+const SENSITIVE_FLAG_PATTERNS: [&str; 4] = ["token", "password", "secret", "api-key"];
+
+fn is_sensitive_long_flag(long: &str) -> bool {
+    let long = long.to_lowercase();
+    SENSITIVE_FLAG_PATTERNS.iter().any(|p| long.contains(p))
+}
+
+/// Short flags of sensitive long flags on the commands `args` runs, read from their
+/// clap definitions since each tool picks its own: `-k` is `--broker-token` on broker
+/// commands, `-t` is `--token` on the verifier and stub server but `--tag` on publish
+fn sensitive_short_flags(args: &[String]) -> Vec<char> {
+    let root = crate::cli::build_cli();
+    let mut command = &root;
+    let mut shorts = Vec::new();
+    let mut args = args.iter().skip(1);
+    loop {
+        for arg in command.get_arguments() {
+            if arg.get_long().is_some_and(is_sensitive_long_flag) {
+                shorts.extend(arg.get_short());
+                shorts.extend(arg.get_all_short_aliases().unwrap_or_default());
+            }
+        }
+        match args.by_ref().find_map(|arg| command.find_subcommand(arg)) {
+            Some(subcommand) => command = subcommand,
+            None => return shorts,
+        }
+    }
+}
+
+fn is_sensitive_flag(flag: &str, short_flags: &[char]) -> bool {
+    if let Some(long) = flag.strip_prefix("--") {
+        is_sensitive_long_flag(long)
+    } else {
+        let mut chars = flag.chars().skip(1);
+        matches!((chars.next(), chars.next()), (Some(short), None) if short_flags.contains(&short))
+    }
+}
+
+/// Whether the value given to `flag` must be hidden. `--user` is the stub server's
+/// `user:password`, so it is only hidden when it carries a password.
+fn should_redact(flag: &str, value: &str, short_flags: &[char]) -> bool {
+    is_sensitive_flag(flag, short_flags) || (flag == "--user" && value.contains(':'))
+}
+
+/// Replace the values of sensitive flags (and any bearer credentials) with `***`
+fn redact_args(args: &[String]) -> Vec<String> {
+    let short_flags = sensitive_short_flags(args);
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut value_of: Option<&str> = None;
+
+    for arg in args {
+        if let Some(flag) = value_of.take() {
+            if should_redact(flag, arg, &short_flags) {
+                redacted.push("***".to_string());
+            } else {
+                redacted.push(arg.clone());
+            }
+        } else if arg.to_lowercase().starts_with("bearer ") {
+            redacted.push("Bearer ***".to_string());
+        } else if let Some((flag, value)) = arg.split_once('=').filter(|(f, _)| f.starts_with('-'))
+        {
+            if should_redact(flag, value, &short_flags) {
+                redacted.push(format!("{}=***", flag));
+            } else {
+                redacted.push(arg.clone());
+            }
+        } else if arg.starts_with('-')
+            && !arg.starts_with("--")
+            && arg.len() > 2
+            && arg.is_char_boundary(2)
+        {
+            // Short flag with an attached value, e.g. -kTOKEN
+            let (flag, value) = arg.split_at(2);
+            if should_redact(flag, value, &short_flags) {
+                redacted.push(format!("{}***", flag));
+            } else {
+                redacted.push(arg.clone());
+            }
+        } else {
+            if is_sensitive_flag(arg, &short_flags) || arg == "--user" {
+                value_of = Some(arg);
+            }
+            redacted.push(arg.clone());
+        }
+    }
+
+    redacted
+}
+
+/// Extra span attributes from `PACT_CLI_OTEL_ATTRS`, a comma separated `key=value` list with
+/// percent-encoded values. Entries without a key are skipped, and a repeated key takes its last
+/// value. `OTEL_RESOURCE_ATTRIBUTES` is left out, the SDK already adds it to the resource.
+fn custom_attributes() -> Vec<KeyValue> {
+    let Ok(value) = std::env::var("PACT_CLI_OTEL_ATTRS") else {
+        return Vec::new();
+    };
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    for (key, value) in value.split(',').filter_map(|pair| pair.split_once('=')) {
+        let key = key.trim();
+        if key.is_empty() {
+            continue;
+        }
+        let value = percent_decode(value.trim());
+        match attributes.iter_mut().find(|(existing, _)| existing == key) {
+            Some(attribute) => attribute.1 = value,
+            None => attributes.push((key.to_string(), value)),
+        }
+    }
+    attributes
+        .into_iter()
+        .map(|(key, value)| KeyValue::new(key, value))
+        .collect()
+}
+
+/// Decode `%XX` escapes, leaving malformed ones as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 pub fn capture_telemetry(args: &[String], exit_code: i32, error_message: Option<&str>) {
+    let args = redact_args(args);
     let span = tracing::Span::current();
     let _enter = span.enter();
     let span_context = span.context();
     let otel_span = span_context.span();
 
-    if let Some(binary) = args.get(0) {
+    // Set first, so they cannot replace the attributes below
+    for attribute in custom_attributes() {
+        otel_span.set_attribute(attribute);
+    }
+    if let Some(binary) = args.first() {
         otel_span.set_attribute(KeyValue::new("binary", binary.clone()));
     }
     if let Some(command) = args.get(1) {
@@ -205,3 +449,89 @@ pub fn capture_telemetry(args: &[String], exit_code: i32, error_message: Option<
         otel_span.set_attribute(KeyValue::new("error_message", message.to_string()));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::redact_args;
+
+    fn redact(args: &[&str]) -> Vec<String> {
+        redact_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn redacts_sensitive_flag_values_in_every_form() {
+        assert_eq!(
+            redact(&["pact", "verifier", "--token", "abc", "--file", "pact.json"]),
+            ["pact", "verifier", "--token", "***", "--file", "pact.json"]
+        );
+        assert_eq!(
+            redact(&["pact", "verifier", "--token=abc"]),
+            ["pact", "verifier", "--token=***"]
+        );
+        assert_eq!(
+            redact(&["pact", "verifier", "-t", "abc"]),
+            ["pact", "verifier", "-t", "***"]
+        );
+        assert_eq!(
+            redact(&["pact", "stub", "-tabc"]),
+            ["pact", "stub", "-t***"]
+        );
+        assert_eq!(
+            redact(&["pact", "broker", "publish", "-k", "abc", "-p", "pw"]),
+            ["pact", "broker", "publish", "-k", "***", "-p", "***"]
+        );
+    }
+
+    #[test]
+    fn short_flags_follow_each_subcommand() {
+        // -t is --tag on publish, and global flags before the command do not hide it
+        assert_eq!(
+            redact(&[
+                "pact",
+                "--log-level",
+                "info",
+                "broker",
+                "publish",
+                "-t",
+                "main"
+            ]),
+            [
+                "pact",
+                "--log-level",
+                "info",
+                "broker",
+                "publish",
+                "-t",
+                "main"
+            ]
+        );
+        assert_eq!(
+            redact(&["pact", "--log-level", "info", "stub", "-t", "abc"]),
+            ["pact", "--log-level", "info", "stub", "-t", "***"]
+        );
+    }
+
+    #[test]
+    fn redacts_user_only_with_a_password() {
+        assert_eq!(
+            redact(&["pact", "stub", "--user", "user:password"]),
+            ["pact", "stub", "--user", "***"]
+        );
+        assert_eq!(
+            redact(&["pact", "stub", "--user=user:password"]),
+            ["pact", "stub", "--user=***"]
+        );
+        assert_eq!(
+            redact(&["pact", "verifier", "--user", "user"]),
+            ["pact", "verifier", "--user", "user"]
+        );
+    }
+
+    #[test]
+    fn redacts_bearer_credentials() {
+        assert_eq!(
+            redact(&["pact", "verifier", "--custom-provider-header", "Bearer abc"]),
+            ["pact", "verifier", "--custom-provider-header", "Bearer ***"]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    borrow::Cow,
+    env,
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::{ArgMatches, Command};
+
+/// Set by `--quiet`, info and progress messages are dropped
+static QUIET: AtomicBool = AtomicBool::new(false);
+/// Set by `--verbose`, commands print extra detail such as the commands they run
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+/// Set by `--no-color` or `NO_COLOR`
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+/// Set by `--no-emoji`, or when the terminal or locale cannot be trusted to show emoji
+static NO_EMOJI: AtomicBool = AtomicBool::new(false);
+/// Set for `--output json`, messages then go to stderr so stdout only carries JSON
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Text tags printed in place of the status emoji. Variants with the U+FE0F
+/// presentation selector come before the bare character.
+const EMOJI_TAGS: [(&str, &str); 17] = [
+    ("⚠️", "[warn]"),
+    ("⚠", "[warn]"),
+    ("ℹ️", "[info]"),
+    ("🗑️", "[removed]"),
+    ("✅", "[ok]"),
+    ("❌", "[error]"),
+    ("🚀", "[run]"),
+    ("📦", "[package]"),
+    ("🔄", "[update]"),
+    ("📋", "[summary]"),
+    ("🔧", "[config]"),
+    ("📌", "[pin]"),
+    ("🔍", "[check]"),
+    ("📥", "[download]"),
+    ("🛑", "[stopped]"),
+    ("🌱", "[seed]"),
+    ("↑", "^"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// Always printed, to stderr
+    Error,
+    /// Always printed, to stderr
+    Warn,
+    /// Results, to stdout (stderr with `--output json`), dropped with `--quiet`
+    Info,
+    /// Steps of a running command, routed like `Info`
+    Progress,
+}
+
+/// Read the output flags from the command line. A flag given at any level of
+/// the subcommand chain counts, so subcommands can keep their own `--output`
+/// and `--verbose` arguments.
+pub fn init(command: &Command, matches: &ArgMatches) {
+    let mut levels = vec![(command, matches)];
+    while let Some((name, sub_matches)) = levels[levels.len() - 1].1.subcommand() {
+        // External subcommands have no definition, and no flags of ours
+        let Some(subcommand) = levels[levels.len() - 1].0.find_subcommand(name) else {
+            break;
+        };
+        levels.push((subcommand, sub_matches));
+    }
+    let flag = |id: &str| {
+        levels.iter().any(|(_, m)| {
+            m.try_get_one::<bool>(id)
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false)
+        })
+    };
+    // Only where `--output` picks a format, `pact mock start --output` is a directory
+    let json = levels.iter().any(|(command, m)| {
+        selects_format(command)
+            && m.try_get_one::<String>("output")
+                .ok()
+                .flatten()
+                .is_some_and(|output| output == "json")
+    });
+    let dumb_terminal = env::var("TERM").is_ok_and(|term| term == "dumb");
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    QUIET.store(flag("quiet"), Ordering::Relaxed);
+    VERBOSE.store(flag("verbose"), Ordering::Relaxed);
+    NO_COLOR.store(
+        flag("no-colour") || no_color_env || dumb_terminal,
+        Ordering::Relaxed,
+    );
+    NO_EMOJI.store(
+        flag("no-emoji") || dumb_terminal || !utf8_locale(),
+        Ordering::Relaxed,
+    );
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Whether `command` has an `output` argument with `json` among its possible values
+fn selects_format(command: &Command) -> bool {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_id() == "output")
+        .is_some_and(|arg| {
+            arg.get_possible_values()
+                .iter()
+                .any(|value| value.get_name() == "json")
+        })
+}
+
+/// The first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set decides. An unset
+/// locale, and Windows, where these are not used, count as UTF-8.
+fn utf8_locale() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+        .is_none_or(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// `message` with its status emoji replaced by text tags when emoji are off
+pub fn text(message: &str) -> Cow<'_, str> {
+    if !NO_EMOJI.load(Ordering::Relaxed) || message.is_ascii() {
+        return Cow::Borrowed(message);
+    }
+    let mut plain = message.to_string();
+    for (emoji, tag) in EMOJI_TAGS {
+        // Narrow emoji are followed by two spaces to line up, a tag needs one
+        plain = plain
+            .replace(&format!("{}  ", emoji), &format!("{} ", tag))
+            .replace(emoji, tag);
+    }
+    Cow::Owned(plain)
+}
+
+/// Print `message` at `level`, see [`Level`] for where each level goes
+pub fn emit(level: Level, message: &str) {
+    let message = text(message);
+    match level {
+        Level::Error | Level::Warn => {
+            // Red for errors and yellow for warnings, on a colour terminal only
+            let color = match level {
+                Level::Error => "31",
+                _ => "33",
+            };
+            if NO_COLOR.load(Ordering::Relaxed) || !std::io::stderr().is_terminal() {
+                eprintln!("{}", message);
+            } else {
+                eprintln!("\x1b[{}m{}\x1b[0m", color, message);
+            }
+        }
+        Level::Info | Level::Progress => {
+            if quiet() {
+                return;
+            }
+            if json() {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+        }
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::cli::output::emit($crate::cli::output::Level::Error, &format!($($arg)*))
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::cli::output::emit($crate::cli::output::Level::Warn, &format!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::cli::output::emit($crate::cli::output::Level::Info, &format!($($arg)*))
+    };
+}
+
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::cli::output::emit($crate::cli::output::Level::Progress, &format!($($arg)*))
+    };
+}
+
+pub(crate) use error;
+pub(crate) use info;
+pub(crate) use progress;
+pub(crate) use warning;
@@ -1,35 +1,124 @@
-use clap::{ArgMatches, Command};
-use std::process::{Command as Cmd, ExitCode};
+use clap::{value_parser, Arg, ArgMatches, Command};
+use std::{
+    fs,
+    io::{IsTerminal, Read, Write},
+    net::TcpStream,
+    process::{Command as Cmd, ExitCode},
+    time::{Duration, Instant},
+};
+
+use crate::cli::output::{self, error, info, progress, warning};
+
+const BROKER_ADDRESS: &str = "127.0.0.1:9292";
+
+/// Passed with `--env` unless the `--env-file` sets the same variable. Docker lets
+/// `--env` win over `--env-file`, so a default is only sent when the file leaves it out.
+const DEFAULT_ENV: [&str; 3] = [
+    "PACT_BROKER_PORT=9292",
+    "PACT_BROKER_DATABASE_URL=sqlite:////tmp/pact_broker.sqlite",
+    "PACT_BROKER_BASE_URL=http://localhost http://localhost http://localhost:9292 http://pact-broker:9292 https://host.docker.internal http://host.docker.internal http://host.docker.internal:9292",
+];
 
 pub fn add_docker_broker_subcommand() -> Command {
     Command::new("docker")
         .about("Run the Pact Broker as a Docker container")
-        .subcommand(Command::new("start").about("Start the Pact Broker as a Docker container"))
+        .subcommand(
+            Command::new("start")
+                .about("Start the Pact Broker as a Docker container")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("OUTPUT")
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help(
+                            "Output format; json prints only {\"url\", \"container_id\"} to stdout",
+                        ),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the docker run command before running it"),
+                )
+                .arg(
+                    Arg::new("env-file")
+                        .long("env-file")
+                        .value_name("PATH")
+                        .help("Env file passed to docker run --env-file; its variables override the built-in PACT_BROKER_* defaults (the port mapping stays 9292)"),
+                )
+                .arg(
+                    Arg::new("wait")
+                        .long("wait")
+                        .action(clap::ArgAction::SetTrue)
+                        .overrides_with("no-wait")
+                        .help("Wait for the broker to report healthy before returning (default)"),
+                )
+                .arg(
+                    Arg::new("no-wait")
+                        .long("no-wait")
+                        .action(clap::ArgAction::SetTrue)
+                        .overrides_with("wait")
+                        .help("Return as soon as the container has been created"),
+                )
+                .arg(
+                    Arg::new("wait-timeout")
+                        .long("wait-timeout")
+                        .value_name("SECONDS")
+                        .value_parser(value_parser!(u64))
+                        .default_value("60")
+                        .help("How long to wait for the broker to become healthy"),
+                ),
+        )
+        .subcommand(
+            Command::new("exec")
+                .visible_alias("shell")
+                .about("Run a command in the running Pact Broker container, a shell by default")
+                .arg(
+                    Arg::new("args")
+                        .value_name("COMMAND")
+                        .help("Command and arguments to run, after --")
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
         .subcommand(Command::new("stop").about("Stop the Pact Broker Docker container"))
         .subcommand(Command::new("remove").about("Remove the Pact Broker Docker container"))
 }
 pub fn run(args: &ArgMatches) -> Result<(), ExitCode> {
     match args.subcommand() {
-        Some(("start", _args)) => {
-            let mut command_args = vec![];
-            command_args.push("run");
-            command_args.push("-d");
-            command_args.push("--name");
-            command_args.push("pact-broker");
-            command_args.push("-p");
-            command_args.push("9292:9292");
-            command_args.push("--env");
-            command_args.push("PACT_BROKER_PORT=9292");
-            command_args.push("--env");
-            command_args.push("PACT_BROKER_DATABASE_URL=sqlite:////tmp/pact_broker.sqlite");
-            command_args.push("--env");
-            command_args.push("'PACT_BROKER_BASE_URL=http://localhost http://localhost http://localhost:9292 http://pact-broker:9292 https://host.docker.internal http://host.docker.internal http://host.docker.internal:9292'");
+        Some(("start", args)) => {
+            let env_file = args.get_one::<String>("env-file");
+            let file_keys = match env_file {
+                Some(path) => match env_file_keys(path) {
+                    Ok(keys) => keys,
+                    Err(message) => {
+                        error!("{}", message);
+                        return Err(ExitCode::from(1));
+                    }
+                },
+                None => Vec::new(),
+            };
+            let mut command_args = vec!["run", "-d", "--name", "pact-broker", "-p", "9292:9292"];
+            for env in DEFAULT_ENV {
+                let key = env.split('=').next().unwrap_or(env);
+                if !file_keys.iter().any(|file_key| file_key == key) {
+                    command_args.extend(["--env", env]);
+                }
+            }
+            if let Some(path) = env_file {
+                command_args.extend(["--env-file", path.as_str()]);
+            }
             command_args.push("pactfoundation/pact-broker:latest");
 
-            println!(
-                "Starting Pact Broker Docker container with command: docker {}",
-                command_args.join(" ")
-            );
+            if output::verbose() {
+                progress!(
+                    "Starting Pact Broker Docker container with command: docker {}",
+                    command_args.join(" ")
+                );
+            }
 
             let output = Cmd::new("docker")
                 .args(&command_args)
@@ -37,14 +126,67 @@ pub fn run(args: &ArgMatches) -> Result<(), ExitCode> {
                 .expect("Failed to execute Docker command");
 
             if output.status.success() {
-                println!("Docker container started successfully");
+                if !args.get_flag("no-wait") {
+                    let timeout = *args.get_one::<u64>("wait-timeout").unwrap();
+                    progress!("Waiting up to {}s for the Pact Broker to start", timeout);
+                    if !wait_until_healthy(Duration::from_secs(timeout)) {
+                        error!(
+                            "Pact Broker did not become healthy within {}s, last container logs:",
+                            timeout
+                        );
+                        print_recent_logs();
+                        return Err(ExitCode::from(1));
+                    }
+                }
+                if output::json() {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "url": "http://localhost:9292",
+                            "container_id": String::from_utf8_lossy(&output.stdout).trim(),
+                        })
+                    );
+                } else {
+                    info!("Docker container started successfully");
+                }
                 Ok(())
             } else {
                 let error_message = String::from_utf8_lossy(&output.stderr);
-                println!("Failed to start Docker container: {}", error_message);
+                error!("Failed to start Docker container: {}", error_message);
                 Err(ExitCode::from(output.status.code().unwrap_or(1) as u8))
             }
         }
+        Some(("exec", args)) => {
+            let running = Cmd::new("docker")
+                .args(["inspect", "-f", "{{.State.Running}}", "pact-broker"])
+                .output()
+                .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "true");
+            if !running {
+                error!("The pact-broker container is not running, start it with 'pact broker docker start'");
+                return Err(ExitCode::from(1));
+            }
+
+            let command: Vec<&str> = args
+                .get_many::<String>("args")
+                .map(|values| values.map(String::as_str).collect())
+                .unwrap_or_else(|| vec!["sh"]);
+            // -t fails when stdin is not a terminal, e.g. when piping into the command
+            let tty_flag = if std::io::stdin().is_terminal() {
+                "-it"
+            } else {
+                "-i"
+            };
+            let status = Cmd::new("docker")
+                .args(["exec", tty_flag, "pact-broker"])
+                .args(&command)
+                .status()
+                .expect("Failed to execute Docker command");
+            if status.success() {
+                Ok(())
+            } else {
+                Err(ExitCode::from(status.code().unwrap_or(1) as u8))
+            }
+        }
         Some(("stop", _args)) => {
             let output = Cmd::new("docker")
                 .arg("stop")
@@ -53,11 +195,11 @@ pub fn run(args: &ArgMatches) -> Result<(), ExitCode> {
                 .expect("Failed to execute Docker command");
 
             if output.status.success() {
-                println!("Docker container stopped successfully");
+                info!("Docker container stopped successfully");
                 Ok(())
             } else {
                 let error_message = String::from_utf8_lossy(&output.stderr);
-                println!("Failed to stop Docker container: {}", error_message);
+                error!("Failed to stop Docker container: {}", error_message);
                 Err(ExitCode::from(1))
             }
         }
@@ -69,18 +211,77 @@ pub fn run(args: &ArgMatches) -> Result<(), ExitCode> {
                 .expect("Failed to execute Docker command");
 
             if output.status.success() {
-                println!("Docker container removed successfully");
+                info!("Docker container removed successfully");
                 Ok(())
             } else {
                 let error_message = String::from_utf8_lossy(&output.stderr);
-                println!("Failed to remove Docker container: {}", error_message);
+                error!("Failed to remove Docker container: {}", error_message);
                 Err(ExitCode::from(1))
             }
         }
         _ => {
-            println!("⚠️  No option provided, try running docker --help");
+            warning!("⚠️  No option provided, try running docker --help");
 
             Ok(())
         }
     }
 }
+
+/// The variable names set by a docker env file, failing if it cannot be read
+fn env_file_keys(path: &str) -> Result<Vec<String>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read env file {}: {}", path, e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split('=').next().unwrap_or(line).trim().to_string())
+        .collect())
+}
+
+/// Poll the broker heartbeat until it answers 200 or `timeout` passes
+fn wait_until_healthy(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if heartbeat_ok() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn heartbeat_ok() -> bool {
+    let Ok(address) = BROKER_ADDRESS.parse() else {
+        return false;
+    };
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, Duration::from_secs(1)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let request = "GET /diagnostic/status/heartbeat HTTP/1.0\r\nHost: localhost\r\n\r\n";
+    let mut response = String::new();
+    if stream.write_all(request.as_bytes()).is_err()
+        || stream.read_to_string(&mut response).is_err()
+    {
+        return false;
+    }
+    response.split_whitespace().nth(1) == Some("200")
+}
+
+/// Print the tail of the container logs, which docker splits over stdout and stderr,
+/// to stderr under the error they explain, so stdout only carries results
+fn print_recent_logs() {
+    match Cmd::new("docker")
+        .args(["logs", "--tail", "20", "pact-broker"])
+        .output()
+    {
+        Ok(output) => {
+            eprint!("{}", String::from_utf8_lossy(&output.stdout));
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        Err(e) => error!("Failed to read the container logs: {}", e),
+    }
+}
@@ -1,11 +1,15 @@
-use clap::{Arg, ArgMatches, Command};
+use clap::{value_parser, Arg, ArgMatches, Command};
 use std::{
     fs,
     io::Read,
-    path::Path,
-    process::{Command as Cmd, ExitStatus},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::{Command as Cmd, ExitCode, ExitStatus, Stdio},
+    time::{Duration, Instant},
 };
 
+use crate::cli::output::{self, error, info, progress, warning};
+
 pub fn add_ruby_broker_subcommand() -> Command {
     Command::new("ruby")
         .about("Install & Run the Pact Broker using system Ruby in $HOME/.pact/pact-broker")
@@ -20,6 +24,33 @@ pub fn add_ruby_broker_subcommand() -> Command {
                         .num_args(0)
                         .action(clap::ArgAction::SetTrue)
                         .help("Enable OpenTelemetry instrumentation for the Pact Broker"),
+                )
+                .arg(
+                    Arg::new("workers")
+                        .long("workers")
+                        .value_name("COUNT")
+                        .value_parser(value_parser!(u32))
+                        .help("Number of Puma worker processes (written to puma.rb)"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .value_name("MIN:MAX")
+                        .value_parser(parse_threads)
+                        .help("Puma thread pool size, either a single count or min:max (written to puma.rb)"),
+                )
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .value_name("HOST")
+                        .help("Host for Puma to bind to [default: 0.0.0.0] (written to puma.rb)"),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .value_parser(value_parser!(u16))
+                        .help("Port for Puma to bind to [default: 9292] (written to puma.rb)"),
                 ),
         )
         .subcommand(
@@ -40,11 +71,76 @@ pub fn add_ruby_broker_subcommand() -> Command {
                         .num_args(0)
                         .action(clap::ArgAction::SetTrue)
                         .help("Enable OpenTelemetry instrumentation for the Pact Broker"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("OUTPUT")
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format; json prints only {\"url\", \"pid\"} to stdout once started"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Publish a couple of example pacts once the broker is up, unless it already has data"),
+                )
+                .arg(
+                    Arg::new("server-command")
+                        .long("server-command")
+                        .value_name("COMMAND")
+                        .default_value("puma")
+                        .help("Server to run with bundle exec; anything other than puma must already be in the bundle and is started without the puma pidfile and puma.rb options"),
+                )
+                .arg(
+                    Arg::new("server-args")
+                        .value_name("ARGS")
+                        .help("Extra arguments for the server, after --")
+                        .num_args(1..)
+                        .last(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("upgrade")
+                .about("Update the pact_broker gem in the installed Pact Broker bundle")
+                .arg(
+                    Arg::new("include-otel")
+                        .long("include-otel")
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Also update the OpenTelemetry gems, if installed"),
+                )
+                .arg(
+                    Arg::new("restart")
+                        .long("restart")
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Stop a running Pact Broker before upgrading and start it again in the background afterwards"),
+                ),
+        )
+        .subcommand(
+            Command::new("exec")
+                .about("Run a command with bundle exec in the Pact Broker bundle, e.g. rake tasks")
+                .arg(
+                    Arg::new("args")
+                        .value_name("COMMAND")
+                        .help("Command and arguments to run, after --")
+                        .num_args(1..)
+                        .required(true)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
                 ),
         )
         .subcommand(Command::new("stop").about("Stop the Pact Broker"))
         .subcommand(Command::new("remove").about("Remove the Pact Broker"))
         .subcommand(Command::new("info").about("Info about the Pact Broker"))
+        .subcommand(
+            Command::new("doctor")
+                .about("Check Ruby, Bundler, the broker install and whether it is running"),
+        )
 }
 
 fn check_ruby_version() -> Result<(), String> {
@@ -190,21 +286,32 @@ end
     Ok(())
 }
 
+/// Where progress output goes; with `--output json` stdout is reserved for the result
+fn progress_stdio() -> Stdio {
+    if output::quiet() {
+        Stdio::null()
+    } else if output::json() {
+        Stdio::from(std::io::stderr())
+    } else {
+        Stdio::inherit()
+    }
+}
+
 pub fn install(otel_enabled: bool) -> Result<ExitStatus, String> {
     check_ruby_version()?;
     check_bundler_installed()?;
-    let home_dir = home::home_dir().ok_or("Could not determine home directory.")?;
-    let broker_dir = home_dir.join(".pact/pact-broker");
+    let broker_dir = broker_dir()?;
 
     write_gemfile_and_config(&broker_dir, otel_enabled)
         .map_err(|e| format!("Failed to write Gemfile/config.ru: {}", e))?;
 
-    println!("🚀 Running bundle install in {}", broker_dir.display());
+    progress!("🚀 Running bundle install in {}", broker_dir.display());
     let status = Cmd::new("ruby")
         .arg("-S")
         .arg("bundle")
         .arg("install")
         .current_dir(&broker_dir)
+        .stdout(progress_stdio())
         .status()
         .map_err(|_| "Failed to run bundle install".to_string())?;
 
@@ -215,65 +322,442 @@ pub fn install(otel_enabled: bool) -> Result<ExitStatus, String> {
     }
 }
 
+fn parse_threads(value: &str) -> Result<String, String> {
+    let counts: Vec<&str> = value.split(':').collect();
+    if counts.len() > 2 || counts.iter().any(|count| count.parse::<u32>().is_err()) {
+        return Err("expected a thread count or min:max, e.g. 5 or 0:16".to_string());
+    }
+    Ok(value.to_string())
+}
+
+/// Write puma.rb from the tuning flags given to install. Returns false without
+/// writing anything when no flags were given, so Puma keeps its own defaults.
+fn write_puma_config(broker_dir: &Path, args: &ArgMatches) -> std::io::Result<bool> {
+    let workers = args.get_one::<u32>("workers");
+    let threads = args.get_one::<String>("threads");
+    let host = args.get_one::<String>("host");
+    let port = args.get_one::<u16>("port");
+    if workers.is_none() && threads.is_none() && host.is_none() && port.is_none() {
+        return Ok(false);
+    }
+
+    let mut puma_config = String::from("# Generated by pact broker ruby install\n");
+    if let Some(workers) = workers {
+        puma_config.push_str(&format!("workers {}\n", workers));
+    }
+    if let Some(threads) = threads {
+        let (min, max) = threads.split_once(':').unwrap_or((threads, threads));
+        puma_config.push_str(&format!("threads {}, {}\n", min, max));
+    }
+    if host.is_some() || port.is_some() {
+        puma_config.push_str(&format!(
+            "bind \"tcp://{}:{}\"\n",
+            host.map(|h| h.as_str()).unwrap_or("0.0.0.0"),
+            port.unwrap_or(&9292)
+        ));
+    }
+
+    fs::create_dir_all(broker_dir)?;
+    fs::write(broker_dir.join("puma.rb"), puma_config)?;
+    Ok(true)
+}
+
+/// The URL the broker is reachable on, taken from the bind line in puma.rb if there is one
+fn broker_url(broker_dir: &Path) -> String {
+    fs::read_to_string(broker_dir.join("puma.rb"))
+        .ok()
+        .and_then(|config| {
+            config.lines().find_map(|line| {
+                let address = line.strip_prefix("bind \"tcp://")?.strip_suffix('"')?;
+                let (host, port) = address.rsplit_once(':')?;
+                let host = if host == "0.0.0.0" { "localhost" } else { host };
+                Some(format!("http://{}:{}", host, port))
+            })
+        })
+        .unwrap_or_else(|| "http://localhost:9292".to_string())
+}
+
+/// `~/.pact/pact-broker`, failing clearly rather than falling back to a relative path
+fn broker_dir() -> Result<PathBuf, String> {
+    home::home_dir()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.join(".pact/pact-broker"))
+        .ok_or_else(|| {
+            "Could not determine the home directory. Set HOME (USERPROFILE on Windows).".to_string()
+        })
+}
+
 fn check_if_installed(broker_dir: &Path) -> bool {
     broker_dir.join("Gemfile").exists() && broker_dir.join("config.ru").exists()
 }
 
-pub fn run(args: &ArgMatches) -> Result<(), String> {
-    let home_dir = home::home_dir().ok_or("Could not determine home directory.")?;
-    let broker_dir = home_dir.join(".pact/pact-broker");
+const OTEL_GEMS: [&str; 6] = [
+    "opentelemetry-api",
+    "opentelemetry-common",
+    "opentelemetry-sdk",
+    "opentelemetry-instrumentation-rack",
+    "opentelemetry-instrumentation-all",
+    "opentelemetry-exporter-otlp",
+];
+
+/// Read the resolved version of a gem from the bundle's Gemfile.lock
+fn locked_gem_version(broker_dir: &Path, gem: &str) -> Option<String> {
+    let lockfile = fs::read_to_string(broker_dir.join("Gemfile.lock")).ok()?;
+    let prefix = format!("    {} (", gem);
+    lockfile
+        .lines()
+        .find(|line| line.starts_with(&prefix))
+        .and_then(|line| line[prefix.len()..].split(')').next())
+        .map(|version| version.to_string())
+}
+
+/// The PID recorded in the pid file, if that process is still alive
+fn running_broker_pid(pid_file_path: &Path) -> Option<u32> {
+    let pid = fs::read_to_string(pid_file_path)
+        .ok()?
+        .trim()
+        .parse::<u32>()
+        .ok()?;
+
+    #[cfg(windows)]
+    let alive = Cmd::new("tasklist")
+        .arg("/FI")
+        .arg(format!("PID eq {}", pid))
+        .arg("/NH")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+        .unwrap_or(false);
+
+    #[cfg(not(windows))]
+    let alive = Cmd::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+
+    if alive {
+        Some(pid)
+    } else {
+        None
+    }
+}
+
+fn upgrade(broker_dir: &Path, include_otel: bool) -> Result<(), String> {
+    check_ruby_version()?;
+    check_bundler_installed()?;
+
+    let before = locked_gem_version(broker_dir, "pact_broker");
+
+    let mut gems = vec!["pact_broker"];
+    if include_otel {
+        let gemfile = fs::read_to_string(broker_dir.join("Gemfile")).unwrap_or_default();
+        gems.extend(OTEL_GEMS.iter().filter(|gem| gemfile.contains(*gem)));
+    }
+
+    progress!(
+        "🚀 Running bundle update {} in {}",
+        gems.join(" "),
+        broker_dir.display()
+    );
+    let status = Cmd::new("ruby")
+        .arg("-S")
+        .arg("bundle")
+        .arg("update")
+        .args(&gems)
+        .current_dir(broker_dir)
+        .status()
+        .map_err(|_| "Failed to run bundle update".to_string())?;
+    if !status.success() {
+        return Err(
+            "⚠️  bundle update failed. Please check your Ruby and Bundler setup.".to_string(),
+        );
+    }
+
+    let after = locked_gem_version(broker_dir, "pact_broker");
+    let display = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".to_string());
+    if before == after {
+        info!("✅ pact_broker is already up to date ({})", display(&after));
+    } else {
+        info!(
+            "✅ pact_broker upgraded from {} to {}",
+            display(&before),
+            display(&after)
+        );
+    }
+    Ok(())
+}
+
+/// Example pacts published by `start --seed`
+const SEED_PACTS: [&str; 2] = [
+    r#"{
+  "consumer": { "name": "Example Web" },
+  "provider": { "name": "Example API" },
+  "interactions": [
+    {
+      "description": "a request for a product",
+      "providerState": "product 10 exists",
+      "request": { "method": "GET", "path": "/products/10" },
+      "response": {
+        "status": 200,
+        "headers": { "Content-Type": "application/json" },
+        "body": { "id": 10, "name": "Pact T-shirt" }
+      }
+    }
+  ],
+  "metadata": { "pactSpecification": { "version": "2.0.0" } }
+}"#,
+    r#"{
+  "consumer": { "name": "Example Mobile" },
+  "provider": { "name": "Example API" },
+  "interactions": [
+    {
+      "description": "a request for all products",
+      "request": { "method": "GET", "path": "/products" },
+      "response": {
+        "status": 200,
+        "headers": { "Content-Type": "application/json" },
+        "body": [{ "id": 10, "name": "Pact T-shirt" }]
+      }
+    }
+  ],
+  "metadata": { "pactSpecification": { "version": "2.0.0" } }
+}"#,
+];
+
+/// Wait for the broker at `url` to answer its heartbeat, then publish `SEED_PACTS`
+/// unless it already knows about some pacticipants
+fn seed_example_data(url: &str) -> Result<String, String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        let client = reqwest::Client::new();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let heartbeat = format!("{}/diagnostic/status/heartbeat", url);
+        loop {
+            match client.get(&heartbeat).send().await {
+                Ok(response) if response.status().is_success() => break,
+                _ if Instant::now() >= deadline => {
+                    return Err(format!("{} did not become ready within 60s", url))
+                }
+                _ => tokio::time::sleep(Duration::from_secs(1)).await,
+            }
+        }
+
+        let pacticipants: serde_json::Value = client
+            .get(format!("{}/pacticipants", url))
+            .header("Accept", "application/hal+json")
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        if pacticipants["_embedded"]["pacticipants"]
+            .as_array()
+            .is_some_and(|existing| !existing.is_empty())
+        {
+            return Ok("🌱 Broker already has data, skipping example pacts".to_string());
+        }
+
+        for pact in SEED_PACTS {
+            let parsed: serde_json::Value =
+                serde_json::from_str(pact).map_err(|e| e.to_string())?;
+            let consumer = parsed["consumer"]["name"].as_str().unwrap_or_default();
+            let provider = parsed["provider"]["name"].as_str().unwrap_or_default();
+            let response = client
+                .put(format!(
+                    "{}/pacts/provider/{}/consumer/{}/version/1.0.0",
+                    url, provider, consumer
+                ))
+                .header("Content-Type", "application/json")
+                .body(pact)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!(
+                    "publishing {} -> {} returned HTTP {}",
+                    consumer,
+                    provider,
+                    response.status()
+                ));
+            }
+        }
+        Ok(format!(
+            "🌱 Published {} example pacts to {}",
+            SEED_PACTS.len(),
+            url
+        ))
+    })
+}
+
+/// Whether something accepts TCP connections on the host and port of `url`
+fn port_responds(url: &str) -> Result<(), String> {
+    let address = url.trim_start_matches("http://");
+    let socket_address = address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| format!("Could not resolve {}", address))?;
+    TcpStream::connect_timeout(&socket_address, Duration::from_secs(2))
+        .map(|_| ())
+        .map_err(|e| format!("Nothing is listening on {}: {}", address, e))
+}
+
+/// Run every check and print one OK/FAIL line each, failing if any check failed
+fn doctor(broker_dir: &Path, pid_file_path: &Path) -> Result<(), String> {
+    let required_file = |name: &str| {
+        let path = broker_dir.join(name);
+        if path.is_file() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} not found, run 'pact broker ruby install'",
+                path.display()
+            ))
+        }
+    };
+    let url = broker_url(broker_dir);
+    let checks: Vec<(String, Result<(), String>)> = vec![
+        ("Ruby 3.1 or newer".to_string(), check_ruby_version()),
+        ("Bundler installed".to_string(), check_bundler_installed()),
+        (
+            format!("Broker directory {}", broker_dir.display()),
+            if broker_dir.is_dir() {
+                Ok(())
+            } else {
+                Err("Directory not found, run 'pact broker ruby install'".to_string())
+            },
+        ),
+        ("Gemfile present".to_string(), required_file("Gemfile")),
+        ("config.ru present".to_string(), required_file("config.ru")),
+        (
+            "Broker process running".to_string(),
+            running_broker_pid(pid_file_path)
+                .map(|_| ())
+                .ok_or_else(|| format!("No live process in {}", pid_file_path.display())),
+        ),
+        (format!("Broker responding on {}", url), port_responds(&url)),
+    ];
+
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => info!("OK    {}", name),
+            Err(reason) => {
+                failed += 1;
+                error!("FAIL  {}: {}", name, reason);
+            }
+        }
+    }
+    if failed == 0 {
+        info!("✅ All {} checks passed", checks.len());
+        Ok(())
+    } else {
+        Err(format!("⚠️  {} of {} checks failed", failed, checks.len()))
+    }
+}
+
+pub fn run(args: &ArgMatches) -> Result<ExitCode, String> {
+    let broker_dir = broker_dir()?;
     let pid_file_path = broker_dir.join("broker.pid");
 
     match args.subcommand() {
         Some(("install", args)) => {
             let otel_enabled = args.get_flag("enable-otel");
+            if write_puma_config(&broker_dir, args)
+                .map_err(|e| format!("Failed to write puma.rb: {}", e))?
+            {
+                info!(
+                    "🚀 Puma config written to {}",
+                    broker_dir.join("puma.rb").display()
+                );
+            }
             if check_if_installed(&broker_dir) {
-                println!(
+                info!(
                     "🚀 Pact Broker is already installed at {}",
                     broker_dir.display()
                 );
-                return Ok(());
+                return Ok(ExitCode::SUCCESS);
             }
-            println!("🚀 Installing Pact Broker...");
+            progress!("🚀 Installing Pact Broker...");
             install(otel_enabled)?;
-            println!("🚀 Pact Broker installed at {}", broker_dir.display());
-            Ok(())
+            info!("🚀 Pact Broker installed at {}", broker_dir.display());
+            Ok(ExitCode::SUCCESS)
         }
         Some(("start", args)) => {
             let otel_enabled = args.get_flag("enable-otel");
+            let server = args.get_one::<String>("server-command").unwrap();
+            let is_puma = server == "puma";
             if !check_if_installed(&broker_dir) {
-                println!("🚀 Pact Broker not found, installing...");
+                if !is_puma {
+                    return Err(
+                        "⚠️  Pact Broker is not installed, run 'pact broker ruby install' first"
+                            .to_string(),
+                    );
+                }
+                progress!("🚀 Pact Broker not found, installing...");
                 install(otel_enabled)?;
             }
-            println!("🚀 Starting Pact Broker with Puma...");
+            progress!("🚀 Starting Pact Broker with {}...", server);
             let mut child_cmd = Cmd::new("ruby");
             child_cmd.arg("-S").arg("bundle");
             child_cmd
                 .arg("exec")
-                .arg("puma")
-                .arg("--pidfile")
-                .arg(&pid_file_path)
-                .current_dir(&broker_dir);
+                .arg(server)
+                .current_dir(&broker_dir)
+                .stdout(progress_stdio());
+            if is_puma {
+                child_cmd.arg("--pidfile").arg(&pid_file_path);
+                if broker_dir.join("puma.rb").exists() {
+                    child_cmd.arg("-C").arg("puma.rb");
+                }
+            }
+            if let Some(server_args) = args.get_many::<String>("server-args") {
+                child_cmd.args(server_args);
+            }
+            let url = broker_url(&broker_dir);
 
             let mut child = child_cmd
                 .spawn()
                 .map_err(|_| "Failed to start Pact Broker".to_string())?;
             let pid = child.id();
-            println!("🚀 Pact Broker is running on http://localhost:9292");
-            println!("🚀 PID: {}", pid);
-            println!("🚀 PID file: {}", pid_file_path.display());
+            if !is_puma {
+                // Only puma writes its own pid file; bundle exec replaces itself with the server
+                fs::write(&pid_file_path, pid.to_string())
+                    .map_err(|e| format!("Failed to write PID file: {}", e))?;
+            }
+            info!("🚀 Pact Broker is running on {}", url);
+            info!("🚀 PID: {}", pid);
+            info!("🚀 PID file: {}", pid_file_path.display());
             let mut pid_file_contents = String::from("unknown");
             while !pid_file_contents.chars().all(char::is_numeric) {
                 std::thread::sleep(std::time::Duration::from_secs(1));
                 pid_file_contents =
                     fs::read_to_string(&pid_file_path).unwrap_or_else(|_| String::from("unknown"));
             }
-            println!("Traveling Broker PID: {}", pid_file_contents);
+            info!("Traveling Broker PID: {}", pid_file_contents);
+            if output::json() {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "url": url,
+                        "pid": pid_file_contents.parse::<u32>().unwrap_or(pid),
+                    })
+                );
+            }
+
+            if args.get_flag("seed") {
+                match seed_example_data(&url) {
+                    Ok(message) => info!("{}", message),
+                    Err(err) => warning!("⚠️  Failed to seed example data: {}", err),
+                }
+            }
 
             let detach = args.get_flag("detach");
             if detach {
-                println!("🚀 Running in the background");
-                return Ok(());
+                info!("🚀 Running in the background");
+                Ok(ExitCode::SUCCESS)
             } else {
                 while child.try_wait().unwrap().is_none() {
                     std::thread::sleep(std::time::Duration::from_secs(1));
@@ -285,7 +769,7 @@ pub fn run(args: &ArgMatches) -> Result<(), String> {
                         let mut pid = String::new();
                         file.read_to_string(&mut pid).unwrap();
                         let pid = pid.trim().parse::<u32>().unwrap();
-                        println!("🚀 Stopping Pact Broker with PID: {}", pid);
+                        progress!("🚀 Stopping Pact Broker with PID: {}", pid);
                         #[cfg(windows)]
                         Cmd::new("taskkill")
                             .arg("/F")
@@ -293,38 +777,97 @@ pub fn run(args: &ArgMatches) -> Result<(), String> {
                             .arg(pid.to_string())
                             .output()
                             .expect("Failed to stop the process");
+
+                        // Puma may already have exited with the child, so a failed kill is fine
+                        #[cfg(not(windows))]
+                        let _ = Cmd::new("kill").arg(pid.to_string()).output();
                     }
                     Err(_) => {
-                        println!("PID file not found");
+                        warning!("PID file not found");
                     }
                 }
                 let _ = fs::remove_file(&pid_file_path);
-                return Ok(());
+                Ok(ExitCode::SUCCESS)
             }
         }
+        Some(("upgrade", args)) => {
+            if !check_if_installed(&broker_dir) {
+                return Err(
+                    "⚠️  Pact Broker is not installed, run 'pact broker ruby install' first"
+                        .to_string(),
+                );
+            }
+
+            let restart = args.get_flag("restart");
+            let running_pid = running_broker_pid(&pid_file_path);
+            if let Some(pid) = running_pid {
+                if !restart {
+                    return Err(format!(
+                        "⚠️  Pact Broker is running (PID {}). Stop it first or pass --restart",
+                        pid
+                    ));
+                }
+                let matches = add_ruby_broker_subcommand().get_matches_from(["ruby", "stop"]);
+                run(&matches)?;
+            } else if restart {
+                info!("🚀 Pact Broker is not running, it will not be restarted");
+            }
+
+            upgrade(&broker_dir, args.get_flag("include-otel"))?;
+
+            // Only bring back a broker that was running before the upgrade
+            if restart && running_pid.is_some() {
+                let matches =
+                    add_ruby_broker_subcommand().get_matches_from(["ruby", "start", "--detach"]);
+                run(&matches)?;
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(("exec", args)) => {
+            if !check_if_installed(&broker_dir) {
+                return Err(
+                    "⚠️  Pact Broker is not installed, run 'pact broker ruby install' first"
+                        .to_string(),
+                );
+            }
+            let command: Vec<&String> = args.get_many::<String>("args").unwrap().collect();
+            let status = Cmd::new("ruby")
+                .arg("-S")
+                .arg("bundle")
+                .arg("exec")
+                .args(&command)
+                .current_dir(&broker_dir)
+                .status()
+                .map_err(|_| "Failed to run bundle exec".to_string())?;
+            Ok(ExitCode::from(status.code().unwrap_or(1) as u8))
+        }
         Some(("stop", _args)) => {
-            let mut file = fs::File::open(&pid_file_path)
+            let pid = fs::read_to_string(&pid_file_path)
                 .map_err(|_| "⚠️ Pact Broker is not running".to_string())?;
-            let mut pid = String::new();
-            file.read_to_string(&mut pid).unwrap();
-            let pid = pid.trim().parse::<u32>().unwrap();
-            println!("🚀 Stopping Pact Broker with PID: {}", pid);
+            let Ok(pid) = pid.trim().parse::<u32>() else {
+                let _ = fs::remove_file(&pid_file_path);
+                return Err(format!(
+                    "⚠️ {} does not hold a process id, removed it. Stop the broker process yourself if it is still running",
+                    pid_file_path.display()
+                ));
+            };
+            progress!("🚀 Stopping Pact Broker with PID: {}", pid);
             #[cfg(windows)]
             Cmd::new("taskkill")
                 .arg("/F")
                 .arg("/PID")
                 .arg(pid.to_string())
                 .output()
-                .expect("⚠️ Failed to stop the broker");
+                .map_err(|e| format!("⚠️ Failed to stop the broker: {}", e))?;
 
             #[cfg(not(windows))]
             Cmd::new("kill")
                 .arg(pid.to_string())
                 .output()
-                .expect("⚠️ Failed to stop the broker");
+                .map_err(|e| format!("⚠️ Failed to stop the broker: {}", e))?;
             let _ = fs::remove_file(&pid_file_path);
-            println!("🛑 Pact Broker stopped");
-            Ok(())
+            info!("🛑 Pact Broker stopped");
+            Ok(ExitCode::SUCCESS)
         }
         Some(("remove", _args)) => {
             let matches = add_ruby_broker_subcommand().get_matches_from(["ruby", "stop"]);
@@ -332,15 +875,15 @@ pub fn run(args: &ArgMatches) -> Result<(), String> {
             if let Ok(metadata) = fs::metadata(&broker_dir) {
                 if metadata.is_dir() {
                     if let Err(err) = fs::remove_dir_all(&broker_dir) {
-                        println!("Failed to remove broker_dir: {}", err);
+                        error!("Failed to remove broker_dir: {}", err);
                     } else {
-                        println!("broker_dir removed successfully");
+                        info!("broker_dir removed successfully");
                     }
                 }
             } else {
-                println!("broker_dir {} not found", broker_dir.display());
+                warning!("broker_dir {} not found", broker_dir.display());
             }
-            Ok(())
+            Ok(ExitCode::SUCCESS)
         }
         Some(("info", _args)) => {
             fn check_directory_exists(directory: &Path) -> bool {
@@ -349,21 +892,21 @@ pub fn run(args: &ArgMatches) -> Result<(), String> {
 
             let pact_broker_ruby_exists = check_directory_exists(&broker_dir);
 
-            println!("Pact broker directory exists: {}", pact_broker_ruby_exists);
+            info!("Pact broker directory exists: {}", pact_broker_ruby_exists);
 
             fn get_ruby_version() -> std::io::Result<String> {
                 let output = Cmd::new("ruby").arg("-v").output()?;
                 Ok(String::from_utf8_lossy(&output.stdout).to_string())
             }
 
-            println!("Ruby version: {:?}", get_ruby_version());
+            info!("Ruby version: {:?}", get_ruby_version());
 
             fn check_pid_file_exists(pid_file_path: &Path) -> bool {
                 pid_file_path.exists()
             }
 
             let pact_broker_pid_file_exists = check_pid_file_exists(&pid_file_path);
-            println!("Pact broker pid exists: {}", pact_broker_pid_file_exists);
+            info!("Pact broker pid exists: {}", pact_broker_pid_file_exists);
 
             fn get_pid_from_file(pid_file_path: &Path) -> Option<u32> {
                 if let Ok(mut file) = fs::File::open(pid_file_path) {
@@ -376,12 +919,13 @@ pub fn run(args: &ArgMatches) -> Result<(), String> {
             }
 
             let pact_broker_pid_exists = get_pid_from_file(&pid_file_path);
-            println!("Pact broker pid: {:?}", pact_broker_pid_exists);
-            Ok(())
+            info!("Pact broker pid: {:?}", pact_broker_pid_exists);
+            Ok(ExitCode::SUCCESS)
         }
+        Some(("doctor", _args)) => doctor(&broker_dir, &pid_file_path).map(|()| ExitCode::SUCCESS),
         _ => {
-            println!("⚠️  No option provided, try running ruby --help");
-            Ok(())
+            warning!("⚠️  No option provided, try running ruby --help");
+            Ok(ExitCode::SUCCESS)
         }
     }
 }